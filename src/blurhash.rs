@@ -0,0 +1,157 @@
+use image::RgbImage;
+use std::f64::consts::PI;
+
+const BASE83_CHARS: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Encodes `img` into a BlurHash string with `x_components` by `y_components` basis
+/// functions (each in `1..=9`), giving clients a cheap inline preview of the result.
+pub fn encode(img: &RgbImage, x_components: u32, y_components: u32) -> String {
+    assert!((1..=9).contains(&x_components));
+    assert!((1..=9).contains(&y_components));
+
+    let width = img.width();
+    let height = img.height();
+
+    let mut factors = Vec::with_capacity((x_components * y_components) as usize);
+    for j in 0..y_components {
+        for i in 0..x_components {
+            let normalization = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            factors.push(multiply_basis_function(img, i, j, normalization));
+        }
+    }
+
+    let dc = factors[0];
+    let ac = &factors[1..];
+
+    let mut hash = String::new();
+
+    let size_flag = (x_components - 1) + (y_components - 1) * 9;
+    hash.push_str(&encode_base83(size_flag as u64, 1));
+
+    let max_ac_value = if ac.is_empty() {
+        0.0
+    } else {
+        ac.iter()
+            .flat_map(|(r, g, b)| [*r, *g, *b])
+            .fold(0.0_f64, |max, v| max.max(v.abs()))
+    };
+    let quantized_max_ac = if max_ac_value > 0.0 {
+        ((max_ac_value * 166.0 - 0.5).floor().max(0.0).min(82.0)) as u64
+    } else {
+        0
+    };
+    hash.push_str(&encode_base83(quantized_max_ac, 1));
+
+    let actual_max_ac = (quantized_max_ac as f64 + 1.0) / 166.0;
+
+    hash.push_str(&encode_base83(encode_dc(dc), 4));
+
+    for &(r, g, b) in ac {
+        hash.push_str(&encode_base83(
+            encode_ac(r, g, b, actual_max_ac),
+            2,
+        ));
+    }
+
+    hash
+}
+
+fn multiply_basis_function(img: &RgbImage, i: u32, j: u32, normalization: f64) -> (f64, f64, f64) {
+    let width = img.width() as f64;
+    let height = img.height() as f64;
+
+    let mut r = 0.0;
+    let mut g = 0.0;
+    let mut b = 0.0;
+
+    for y in 0..img.height() {
+        for x in 0..img.width() {
+            let basis = (PI * i as f64 * x as f64 / width).cos()
+                * (PI * j as f64 * y as f64 / height).cos();
+
+            let pixel = img.get_pixel(x, y);
+            r += basis * srgb_to_linear(pixel[0]);
+            g += basis * srgb_to_linear(pixel[1]);
+            b += basis * srgb_to_linear(pixel[2]);
+        }
+    }
+
+    let scale = normalization / (width * height);
+    (r * scale, g * scale, b * scale)
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let c = value as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let c = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+fn encode_dc((r, g, b): (f64, f64, f64)) -> u64 {
+    let r = linear_to_srgb(r) as u64;
+    let g = linear_to_srgb(g) as u64;
+    let b = linear_to_srgb(b) as u64;
+    (r << 16) + (g << 8) + b
+}
+
+fn encode_ac(r: f64, g: f64, b: f64, max_ac: f64) -> u64 {
+    let quant = |v: f64| -> u64 {
+        let normalized = (v / max_ac).signum() * (v / max_ac).abs().powf(0.5);
+        ((normalized * 9.0 + 9.5).floor().clamp(0.0, 18.0)) as u64
+    };
+
+    quant(r) * 19 * 19 + quant(g) * 19 + quant(b)
+}
+
+fn encode_base83(mut value: u64, length: usize) -> String {
+    let mut result = vec![0u8; length];
+    for i in (0..length).rev() {
+        let digit = (value % 83) as usize;
+        result[i] = BASE83_CHARS[digit];
+        value /= 83;
+    }
+    String::from_utf8(result).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base83_encodes_known_values() {
+        assert_eq!(encode_base83(0, 1), "0");
+        assert_eq!(encode_base83(82, 1), "~");
+        assert_eq!(encode_base83(1, 2), "01");
+        assert_eq!(encode_base83(83, 2), "10");
+    }
+
+    #[test]
+    fn dc_packs_known_colors() {
+        assert_eq!(encode_dc((0.0, 0.0, 0.0)), 0);
+        assert_eq!(encode_dc((1.0, 1.0, 1.0)), 0xFF_FF_FF);
+    }
+
+    // Regression coverage for the abs-magnitude/floor-quantization bug fixed in fa71059:
+    // a naive `.round()` (instead of `.floor()`) would push the zero case to 10 instead of 9
+    // and the negative-max case to 1 instead of 0 (the max-magnitude case's 18.5 would round
+    // to 19, which the clamp pulls back to 18).
+    #[test]
+    fn ac_quantizes_known_magnitudes() {
+        assert_eq!(encode_ac(0.0, 0.0, 0.0, 1.0), 9 * 19 * 19 + 9 * 19 + 9);
+        assert_eq!(encode_ac(1.0, 1.0, 1.0, 1.0), 18 * 19 * 19 + 18 * 19 + 18);
+        assert_eq!(encode_ac(-1.0, -1.0, -1.0, 1.0), 0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use sha2::{Digest, Sha256};
+
+/// On-disk cache for encoded web responses, keyed by the normalized request params that
+/// produced them (url, scale, format), so a repeat request for the same output is served
+/// straight from disk instead of re-running detection.
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+pub struct CacheHit {
+    pub bytes: Vec<u8>,
+    pub modified: SystemTime,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).ok();
+        Self { dir, ttl }
+    }
+
+    pub fn key(url: &str, scale: f32, format: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        hasher.update(scale.to_bits().to_be_bytes());
+        hasher.update(format.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<CacheHit> {
+        let path = self.path(key);
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()?;
+        if modified.elapsed().ok()? > self.ttl {
+            return None;
+        }
+
+        let bytes = fs::read(&path).ok()?;
+        Some(CacheHit { bytes, modified })
+    }
+
+    pub fn put(&self, key: &str, bytes: &[u8]) {
+        let _ = fs::write(self.path(key), bytes);
+    }
+
+    pub fn ttl_seconds(&self) -> u64 {
+        self.ttl.as_secs()
+    }
+
+    /// Deletes entries past the TTL, then if the directory is still over `max_total_bytes`,
+    /// removes the oldest remaining entries until it's back under budget.
+    pub fn evict(&self, max_total_bytes: u64) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        let mut files: Vec<(PathBuf, SystemTime, u64)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let modified = metadata.modified().ok()?;
+                Some((entry.path(), modified, metadata.len()))
+            })
+            .collect();
+
+        files.retain(|(path, modified, _)| {
+            if modified.elapsed().unwrap_or_default() > self.ttl {
+                let _ = fs::remove_file(path);
+                false
+            } else {
+                true
+            }
+        });
+
+        let mut total: u64 = files.iter().map(|(_, _, size)| size).sum();
+        if total <= max_total_bytes {
+            return;
+        }
+
+        files.sort_by_key(|(_, modified, _)| *modified);
+        for (path, _, size) in files {
+            if total <= max_total_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(size);
+            }
+        }
+    }
+}
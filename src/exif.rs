@@ -0,0 +1,85 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+
+/// Reads the EXIF `Orientation` tag from the original file bytes (if any) and rotates/flips
+/// `img` so it is upright before face detection runs. `rustface` has no notion of EXIF
+/// orientation, so a sideways-stored portrait photo would otherwise reach it with faces
+/// rotated away from what the detector was trained on.
+pub fn auto_orient(img: DynamicImage, bytes: &[u8]) -> DynamicImage {
+    match read_orientation(bytes) {
+        Some(orientation) => apply_orientation(img, orientation),
+        None => img,
+    }
+}
+
+fn read_orientation(bytes: &[u8]) -> Option<u32> {
+    let mut cursor = Cursor::new(bytes);
+    let exif = exif::Reader::new()
+        .read_from_container(&mut cursor)
+        .ok()?;
+    let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+    field.value.get_uint(0)
+}
+
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Finds the `APP1`/Exif segment in the original JPEG `source` bytes (if any) and splices it
+/// into `encoded`, a freshly re-encoded JPEG, right after the SOI marker. Used to preserve
+/// EXIF metadata across a strangify round-trip when `--strip-exif` isn't passed.
+pub fn copy_jpeg_exif(source: &[u8], encoded: Vec<u8>) -> Vec<u8> {
+    let Some(segment) = find_app1_exif_segment(source) else {
+        return encoded;
+    };
+
+    if encoded.len() < 2 || encoded[0..2] != [0xFF, 0xD8] {
+        return encoded;
+    }
+
+    let mut out = Vec::with_capacity(encoded.len() + segment.len());
+    out.extend_from_slice(&encoded[0..2]);
+    out.extend_from_slice(segment);
+    out.extend_from_slice(&encoded[2..]);
+    out
+}
+
+fn find_app1_exif_segment(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.len() < 4 || bytes[0..2] != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            break;
+        }
+        let marker = bytes[pos + 1];
+        if marker == 0xD8 || marker == 0xD9 {
+            break;
+        }
+        let segment_len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment_end = pos + 2 + segment_len;
+        if segment_end > bytes.len() {
+            break;
+        }
+
+        if marker == 0xE1 && bytes[pos + 4..].starts_with(b"Exif\0\0") {
+            return Some(&bytes[pos..segment_end]);
+        }
+
+        pos = segment_end;
+    }
+
+    None
+}
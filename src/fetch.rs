@@ -0,0 +1,208 @@
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+#[derive(Debug)]
+pub enum FetchError {
+    InvalidUrl,
+    Forbidden(String),
+    TooLarge,
+    NotAnImage,
+    DimensionsTooLarge,
+    TooManyRedirects,
+    Network(String),
+}
+
+impl FetchError {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            FetchError::InvalidUrl | FetchError::TooManyRedirects => 400,
+            FetchError::Forbidden(_) => 403,
+            FetchError::TooLarge | FetchError::DimensionsTooLarge => 413,
+            FetchError::NotAnImage => 415,
+            FetchError::Network(_) => 502,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            FetchError::InvalidUrl => "Invalid URL".to_string(),
+            FetchError::Forbidden(reason) => format!("Refusing to fetch url: {reason}"),
+            FetchError::TooLarge => "Remote image exceeds max-download-bytes".to_string(),
+            FetchError::NotAnImage => "Remote content is not an image".to_string(),
+            FetchError::DimensionsTooLarge => "Remote image exceeds the max pixel dimensions".to_string(),
+            FetchError::TooManyRedirects => "Too many redirects".to_string(),
+            FetchError::Network(reason) => format!("Failed to fetch url: {reason}"),
+        }
+    }
+}
+
+const MAX_REDIRECTS: u8 = 5;
+
+/// Guards `get_url_image` against SSRF and resource-exhaustion.
+///
+/// The host check lives inside a custom `ureq::Resolver` rather than as a separate
+/// pre-connect lookup: ureq calls it at the moment it actually opens the TCP connection, so
+/// there is no gap between "checked" and "connected" for a DNS-rebinding attacker to land a
+/// different address in. Redirects are followed one hop at a time instead of automatically,
+/// so every hop's host goes through that same resolver before a connection is made.
+pub struct FetchGuard {
+    pub allow_private: bool,
+    pub max_download_bytes: u64,
+    pub max_pixels: u64,
+}
+
+impl FetchGuard {
+    pub fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        let agent = ureq::AgentBuilder::new()
+            .resolver(GuardedResolver {
+                allow_private: self.allow_private,
+            })
+            .redirects(0)
+            .build();
+
+        let mut current = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let response = agent.get(&current).call().map_err(map_ureq_error)?;
+
+            if let Some(location) = redirect_location(&response) {
+                current = location;
+                continue;
+            }
+
+            let content_type = response.content_type().to_string();
+            if !content_type.starts_with("image/") {
+                return Err(FetchError::NotAnImage);
+            }
+
+            let mut limited = response.into_reader().take(self.max_download_bytes + 1);
+            let mut buffer = Vec::new();
+            limited
+                .read_to_end(&mut buffer)
+                .map_err(|err| FetchError::Network(err.to_string()))?;
+
+            if buffer.len() as u64 > self.max_download_bytes {
+                return Err(FetchError::TooLarge);
+            }
+
+            return Ok(buffer);
+        }
+
+        Err(FetchError::TooManyRedirects)
+    }
+
+    pub fn check_dimensions(&self, width: u32, height: u32) -> Result<(), FetchError> {
+        if (width as u64) * (height as u64) > self.max_pixels {
+            return Err(FetchError::DimensionsTooLarge);
+        }
+        Ok(())
+    }
+}
+
+fn redirect_location(response: &ureq::Response) -> Option<String> {
+    if !(300..400).contains(&response.status()) {
+        return None;
+    }
+    response.header("Location").map(|location| location.to_string())
+}
+
+fn map_ureq_error(err: ureq::Error) -> FetchError {
+    match err {
+        ureq::Error::Status(status, _) => FetchError::Network(format!("upstream returned {status}")),
+        ureq::Error::Transport(transport) => {
+            let is_forbidden = std::error::Error::source(&transport)
+                .and_then(|source| source.downcast_ref::<io::Error>())
+                .is_some_and(|io_err| io_err.kind() == io::ErrorKind::PermissionDenied);
+
+            if is_forbidden {
+                FetchError::Forbidden(transport.to_string())
+            } else {
+                FetchError::Network(transport.to_string())
+            }
+        }
+    }
+}
+
+/// Resolves `netloc` ("host:port") and rejects loopback, private, link-local, broadcast,
+/// documentation, unspecified and IPv6 unique-local addresses (unless `allow_private` is set);
+/// other special-purpose ranges such as multicast or 100.64.0.0/10 are not rejected. This is the only place DNS is resolved for a fetch, so
+/// there is a single source of truth for "which address did we actually connect to".
+struct GuardedResolver {
+    allow_private: bool,
+}
+
+impl ureq::Resolver for GuardedResolver {
+    fn resolve(&self, netloc: &str) -> io::Result<Vec<SocketAddr>> {
+        let addrs: Vec<SocketAddr> = netloc.to_socket_addrs()?.collect();
+
+        if !self.allow_private {
+            for addr in &addrs {
+                if is_disallowed(addr.ip()) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("{} is a loopback/private/link-local address", addr.ip()),
+                    ));
+                }
+            }
+        }
+
+        Ok(addrs)
+    }
+}
+
+fn is_disallowed(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_disallowed_v4(v4),
+        // IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) must be checked against the v4 rules,
+        // since `Ipv6Addr::is_loopback`/`is_unspecified` only recognize the native `::1`/`::` forms.
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(mapped) => is_disallowed_v4(mapped),
+            None => {
+                v6.is_loopback() || v6.is_unspecified() || is_unique_local_v6(v6) || is_link_local_v6(v6)
+            }
+        },
+    }
+}
+
+fn is_disallowed_v4(v4: Ipv4Addr) -> bool {
+    v4.is_loopback()
+        || v4.is_private()
+        || v4.is_link_local()
+        || v4.is_broadcast()
+        || v4.is_documentation()
+        || v4.is_unspecified()
+}
+
+fn is_unique_local_v6(ip: Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xfe00) == 0xfc00
+}
+
+fn is_link_local_v6(ip: Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_disallowed_classifies_known_addresses() {
+        let cases: &[(&str, bool)] = &[
+            ("127.0.0.1", true),
+            ("169.254.169.254", true),
+            ("10.0.0.1", true),
+            ("::1", true),
+            ("::ffff:127.0.0.1", true),
+            ("fc00::1", true),
+            ("8.8.8.8", false),
+        ];
+
+        for (addr, expected) in cases {
+            let ip: IpAddr = addr.parse().expect("valid test address");
+            assert_eq!(
+                is_disallowed(ip),
+                *expected,
+                "is_disallowed({addr}) should be {expected}"
+            );
+        }
+    }
+}
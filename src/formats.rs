@@ -0,0 +1,59 @@
+use image::ImageFormat;
+
+/// Maps a user-facing format name (CLI `--format` value or query param) to the matching
+/// `image::ImageFormat`. Returns `None` for anything we don't support so callers can fall
+/// back to content negotiation or a default.
+pub fn format_from_name(name: &str) -> Option<ImageFormat> {
+    match name.to_ascii_lowercase().as_str() {
+        "jpeg" | "jpg" => Some(ImageFormat::Jpeg),
+        "png" => Some(ImageFormat::Png),
+        "webp" => Some(ImageFormat::WebP),
+        "avif" => Some(ImageFormat::Avif),
+        "gif" => Some(ImageFormat::Gif),
+        _ => None,
+    }
+}
+
+pub fn mime_type(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Png => "image/png",
+        ImageFormat::WebP => "image/webp",
+        ImageFormat::Avif => "image/avif",
+        ImageFormat::Gif => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        ImageFormat::Avif => "avif",
+        ImageFormat::Gif => "gif",
+        _ => "img",
+    }
+}
+
+/// Picks an output format: an explicit `format` value wins if it's one we recognize,
+/// otherwise the first recognized `image/*` type in the order an `Accept` header lists them
+/// (q-values are not considered), otherwise JPEG.
+pub fn negotiate(explicit: Option<&str>, accept: Option<&str>) -> ImageFormat {
+    if let Some(format) = explicit.and_then(format_from_name) {
+        return format;
+    }
+
+    if let Some(accept) = accept {
+        for candidate in accept.split(',') {
+            let candidate = candidate.split(';').next().unwrap_or("").trim();
+            if let Some(name) = candidate.strip_prefix("image/") {
+                if let Some(format) = format_from_name(name) {
+                    return format;
+                }
+            }
+        }
+    }
+
+    ImageFormat::Jpeg
+}
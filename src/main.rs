@@ -1,21 +1,67 @@
 use std::io::Read;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{ffi::OsStr, io::Cursor};
 
-use axum::{extract::Query, http::header, response::IntoResponse, routing::get, Router};
-use clap::{ArgAction, Parser};
-use image::{
-    imageops::{overlay, FilterType},
-    DynamicImage,
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Query, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    routing::get,
+    Router,
 };
+use clap::{ArgAction, Parser};
+use image::DynamicImage;
 
-use rand::random;
-use rustface::{read_model, ImageData};
 use serde_derive::Deserialize;
 use uuid::Uuid;
 
+mod blurhash;
+mod cache;
+mod exif;
+mod fetch;
+mod formats;
+mod strangify;
+mod video;
+
+use cache::Cache;
+use fetch::FetchGuard;
+
+use strangify::strangify;
+
 const DEFAULT_SCALE: f32 = 0.55_f32;
 
+/// Maps internal errors to a clean HTTP status instead of letting `root`/`upload` panic.
+struct ApiError {
+    status: axum::http::StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: axum::http::StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status, self.message).into_response()
+    }
+}
+
+impl From<fetch::FetchError> for ApiError {
+    fn from(err: fetch::FetchError) -> Self {
+        Self::new(
+            axum::http::StatusCode::from_u16(err.status_code()).unwrap(),
+            err.message(),
+        )
+    }
+}
+
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(short, long, required_unless_present_any=["url", "web_server"])]
@@ -33,6 +79,30 @@ struct Args {
     #[arg(long, default_value_t = 8080)]
     port: usize,
 
+    #[arg(long)]
+    format: Option<String>,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    strip_exif: bool,
+
+    #[arg(long, default_value = "./cache")]
+    cache_dir: String,
+
+    #[arg(long, default_value_t = 3600)]
+    cache_ttl: u64,
+
+    #[arg(long, default_value_t = 500_000_000)]
+    cache_max_bytes: u64,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    allow_private: bool,
+
+    #[arg(long, default_value_t = 25_000_000)]
+    max_download_bytes: u64,
+
+    #[arg(long, default_value_t = 40_000_000)]
+    max_pixels: u64,
+
     #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
     _args: Vec<String>,
 }
@@ -47,8 +117,31 @@ async fn main() {
     }
 }
 
+#[derive(Clone)]
+struct AppState {
+    cache: Arc<Cache>,
+    cache_max_bytes: u64,
+    fetch_guard: Arc<FetchGuard>,
+}
+
 async fn web(args: Args) {
-    let app = Router::new().route("/", get(root));
+    let state = AppState {
+        cache: Arc::new(Cache::new(
+            args.cache_dir.clone(),
+            Duration::from_secs(args.cache_ttl),
+        )),
+        cache_max_bytes: args.cache_max_bytes,
+        fetch_guard: Arc::new(FetchGuard {
+            allow_private: args.allow_private,
+            max_download_bytes: args.max_download_bytes,
+            max_pixels: args.max_pixels,
+        }),
+    };
+
+    let app = Router::new()
+        .route("/", get(root).post(upload))
+        .layer(DefaultBodyLimit::max(args.max_download_bytes as usize))
+        .with_state(state);
     let server_listening_on = format!("0.0.0.0:{}", args.port);
     let listener = tokio::net::TcpListener::bind(&server_listening_on)
         .await
@@ -61,121 +154,390 @@ async fn web(args: Args) {
 struct RootParams {
     url: String,
     scale: Option<f32>,
+    format: Option<String>,
+    blurhash: Option<bool>,
 }
 
-async fn root(query: Option<Query<RootParams>>) -> impl IntoResponse {
-    if let Some(query) = query {
-        let Query(params) = query;
-        let (mut img, stem, _) = get_url_image(params.url);
+async fn root(
+    State(state): State<AppState>,
+    query: Option<Query<RootParams>>,
+    headers: HeaderMap,
+) -> Result<(Vec<(header::HeaderName, String)>, Vec<u8>), ApiError> {
+    let Some(Query(params)) = query else {
+        return Err(ApiError::new(
+            axum::http::StatusCode::BAD_REQUEST,
+            "No query params",
+        ));
+    };
 
-        strangify(&mut img, params.scale.unwrap_or(DEFAULT_SCALE));
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let format = formats::negotiate(params.format.as_deref(), accept);
+    let scale = params.scale.unwrap_or(DEFAULT_SCALE);
+    let want_blurhash = params.blurhash.unwrap_or(false);
+    let (stem, _) = get_filename_and_extension(&params.url);
+
+    let cache_key = Cache::key(&params.url, scale, formats::extension(format));
+
+    if let Some(hit) = state.cache.get(&cache_key) {
+        let mut response_headers = base_headers(format, &stem);
+        response_headers.push((
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", state.cache.ttl_seconds()),
+        ));
+        response_headers.push((header::LAST_MODIFIED, httpdate::fmt_http_date(hit.modified)));
+        if want_blurhash {
+            if let Ok(cached_img) = image::load_from_memory(&hit.bytes) {
+                response_headers.push((
+                    header::HeaderName::from_static("x-blurhash"),
+                    blurhash::encode(&cached_img.to_rgb8(), 4, 3),
+                ));
+            }
+        }
+        return Ok((response_headers, hit.bytes));
+    }
+
+    let buffer = state.fetch_guard.fetch(&params.url)?;
 
-        let bytes = vec![];
-        let mut cursor = Cursor::new(bytes);
-        match img.write_to(&mut cursor, image::ImageFormat::Jpeg) {
-            Ok(_) => {
-                let headers = [
-                    (header::CONTENT_TYPE, format!("image/jpeg")),
-                    (
-                        header::CONTENT_DISPOSITION,
-                        format!("attachment; filename=\"{}.jpg\"", stem),
-                    ),
-                ];
+    if params.format.is_none() && video::is_gif(&buffer) {
+        let state = state.clone();
+        let url = params.url.clone();
+        return tokio::task::spawn_blocking(move || {
+            serve_gif(&state, &buffer, &url, scale, want_blurhash, &stem)
+        })
+        .await
+        .expect("blocking task panicked");
+    }
 
-                let mut out = Vec::new();
-                cursor.set_position(0);
-                cursor.read_to_end(&mut out).unwrap();
+    let task_state = state.clone();
+    let task_stem = stem.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut img = decode_image(&buffer, &task_state.fetch_guard)?;
+        strangify(&mut img, scale);
+
+        let mut extra_headers = vec![(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", task_state.cache.ttl_seconds()),
+        )];
+        if want_blurhash {
+            extra_headers.push((
+                header::HeaderName::from_static("x-blurhash"),
+                blurhash::encode(&img.to_rgb8(), 4, 3),
+            ));
+        }
+
+        let (response_headers, bytes) = encode_response(&img, format, &task_stem, extra_headers)?;
+
+        task_state.cache.put(&cache_key, &bytes);
+        task_state.cache.evict(task_state.cache_max_bytes);
+
+        Ok((response_headers, bytes))
+    })
+    .await
+    .expect("blocking task panicked")
+}
 
-                return Ok((headers, out));
+/// Handles the animated-GIF case for `root`: cached under a `"gif-anim"` key distinct from
+/// `formats::extension(ImageFormat::Gif)`'s `"gif"`, since `?format=gif` deliberately bypasses
+/// this function to strangify just the first frame — the two code paths must never share a
+/// cache bucket, or whichever ran first wins it for both. Runs inside `spawn_blocking`: the
+/// per-frame `Strangifier` loop and `Cache::evict`'s directory walk are both CPU/IO-bound work
+/// that would otherwise stall the tokio worker thread for the whole request.
+fn serve_gif(
+    state: &AppState,
+    buffer: &[u8],
+    url: &str,
+    scale: f32,
+    want_blurhash: bool,
+    stem: &str,
+) -> Result<(Vec<(header::HeaderName, String)>, Vec<u8>), ApiError> {
+    let cache_key = Cache::key(url, scale, "gif-anim");
+
+    if let Some(hit) = state.cache.get(&cache_key) {
+        let mut response_headers = base_headers(image::ImageFormat::Gif, stem);
+        response_headers.push((
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", state.cache.ttl_seconds()),
+        ));
+        response_headers.push((header::LAST_MODIFIED, httpdate::fmt_http_date(hit.modified)));
+        if want_blurhash {
+            if let Ok(cached_img) = image::load_from_memory(&hit.bytes) {
+                response_headers.push((
+                    header::HeaderName::from_static("x-blurhash"),
+                    blurhash::encode(&cached_img.to_rgb8(), 4, 3),
+                ));
             }
-            Err(_) => return Err("Failed to encode image"),
-        };
-    } else {
-        Err("No query params")
+        }
+        return Ok((response_headers, hit.bytes));
+    }
+
+    let (width, height) = image::io::Reader::new(Cursor::new(buffer))
+        .with_guessed_format()
+        .map_err(|_| fetch::FetchError::NotAnImage)?
+        .into_dimensions()
+        .map_err(|_| fetch::FetchError::NotAnImage)?;
+    state.fetch_guard.check_dimensions(width, height)?;
+
+    let out = video::strangify_gif(buffer, scale);
+
+    let mut response_headers = base_headers(image::ImageFormat::Gif, stem);
+    response_headers.push((
+        header::CACHE_CONTROL,
+        format!("public, max-age={}", state.cache.ttl_seconds()),
+    ));
+    if want_blurhash {
+        if let Ok(decoded) = image::load_from_memory(&out) {
+            response_headers.push((
+                header::HeaderName::from_static("x-blurhash"),
+                blurhash::encode(&decoded.to_rgb8(), 4, 3),
+            ));
+        }
+    }
+
+    state.cache.put(&cache_key, &out);
+    state.cache.evict(state.cache_max_bytes);
+
+    Ok((response_headers, out))
+}
+
+fn base_headers(format: image::ImageFormat, stem: &str) -> Vec<(header::HeaderName, String)> {
+    vec![
+        (header::CONTENT_TYPE, formats::mime_type(format).to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                stem,
+                formats::extension(format)
+            ),
+        ),
+    ]
+}
+
+fn encode_response(
+    img: &DynamicImage,
+    format: image::ImageFormat,
+    stem: &str,
+    extra_headers: Vec<(header::HeaderName, String)>,
+) -> Result<(Vec<(header::HeaderName, String)>, Vec<u8>), ApiError> {
+    let mut cursor = Cursor::new(Vec::new());
+    match img.write_to(&mut cursor, format) {
+        Ok(_) => {
+            let mut headers = base_headers(format, stem);
+            headers.extend(extra_headers);
+
+            let mut out = Vec::new();
+            cursor.set_position(0);
+            cursor.read_to_end(&mut out).unwrap();
+
+            Ok((headers, out))
+        }
+        Err(_) => Err(ApiError::new(
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to encode image",
+        )),
     }
 }
 
+/// Accepts a `multipart/form-data` upload with an image part and an optional `scale` field,
+/// so clients can POST image bytes directly instead of pointing `root` at a public URL.
+async fn upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<(Vec<(header::HeaderName, String)>, Vec<u8>), ApiError> {
+    let mut bytes: Option<Vec<u8>> = None;
+    let mut scale = DEFAULT_SCALE;
+
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name().unwrap_or_default() {
+            "scale" => {
+                if let Ok(text) = field.text().await {
+                    if let Ok(parsed) = text.parse() {
+                        scale = parsed;
+                    }
+                }
+            }
+            _ => {
+                if let Ok(data) = field.bytes().await {
+                    bytes = Some(data.to_vec());
+                }
+            }
+        }
+    }
+
+    let Some(bytes) = bytes else {
+        return Err(ApiError::new(
+            axum::http::StatusCode::BAD_REQUEST,
+            "No image part in upload",
+        ));
+    };
+
+    if bytes.len() as u64 > state.fetch_guard.max_download_bytes {
+        return Err(ApiError::new(
+            axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+            "Uploaded image exceeds max-download-bytes",
+        ));
+    }
+
+    let (width, height) = image::io::Reader::new(Cursor::new(&bytes))
+        .with_guessed_format()
+        .map_err(|_| {
+            ApiError::new(
+                axum::http::StatusCode::BAD_REQUEST,
+                "Failed to decode uploaded image",
+            )
+        })?
+        .into_dimensions()
+        .map_err(|_| {
+            ApiError::new(
+                axum::http::StatusCode::BAD_REQUEST,
+                "Failed to decode uploaded image",
+            )
+        })?;
+    state.fetch_guard.check_dimensions(width, height).map_err(
+        |err| ApiError::new(axum::http::StatusCode::from_u16(err.status_code()).unwrap(), err.message()),
+    )?;
+
+    if video::is_gif(&bytes) {
+        let out = tokio::task::spawn_blocking(move || video::strangify_gif(&bytes, scale))
+            .await
+            .expect("blocking task panicked");
+        return Ok((base_headers(image::ImageFormat::Gif, "upload"), out));
+    }
+
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let format = formats::negotiate(None, accept);
+
+    tokio::task::spawn_blocking(move || {
+        let img = image::io::Reader::new(Cursor::new(&bytes))
+            .with_guessed_format()
+            .unwrap()
+            .decode()
+            .map_err(|_| {
+                ApiError::new(
+                    axum::http::StatusCode::BAD_REQUEST,
+                    "Failed to decode uploaded image",
+                )
+            })?;
+        let mut img = exif::auto_orient(img, &bytes);
+
+        strangify(&mut img, scale);
+
+        encode_response(&img, format, "upload", Vec::new())
+    })
+    .await
+    .expect("blocking task panicked")
+}
+
 fn local(args: Args) {
-    let (mut img, filename, extension) = if let Some(path) = args.path {
+    let format_override = args.format.as_deref().and_then(formats::format_from_name);
+
+    if let Some(path) = &args.path {
+        let (_, extension) = get_filename_and_extension(path);
+        if extension.eq_ignore_ascii_case("gif") && format_override.is_none() {
+            return local_gif(path, &args);
+        }
+        #[cfg(feature = "video-ffmpeg")]
+        if matches!(extension.to_ascii_lowercase().as_str(), "mp4" | "webm") && format_override.is_none() {
+            return local_video(path, &args);
+        }
+    }
+
+    let (mut img, filename, extension, source_bytes) = if let Some(path) = args.path {
         let (stem, extension) = get_filename_and_extension(&path);
-        let img = image::open(path).expect("No such file or directory");
-        (img, stem, extension)
+        let bytes = std::fs::read(&path).expect("No such file or directory");
+        let img = image::load_from_memory(&bytes).expect("Not a valid image");
+        let img = exif::auto_orient(img, &bytes);
+        (img, stem, extension, bytes)
     } else if let Some(url) = args.url {
-        get_url_image(url)
+        let guard = FetchGuard {
+            allow_private: args.allow_private,
+            max_download_bytes: args.max_download_bytes,
+            max_pixels: args.max_pixels,
+        };
+        get_url_image(url, &guard).unwrap_or_else(|err| panic!("{}", err.message()))
     } else {
         panic!("no image available");
     };
 
     strangify(&mut img, args.scale);
 
+    let (extension, format) = match format_override {
+        Some(format) => (formats::extension(format).to_string(), Some(format)),
+        None => (extension, None),
+    };
+
     let id = Uuid::new_v4();
     let output_filename = format!("./{}_{}.{}", filename, id.to_string(), extension);
     println!("{output_filename}");
 
-    img.save(output_filename).unwrap();
+    let output_format = format.unwrap_or(image::ImageFormat::from_path(&output_filename).unwrap());
+    let mut cursor = Cursor::new(Vec::new());
+    img.write_to(&mut cursor, output_format).unwrap();
+    let mut out = cursor.into_inner();
+
+    if !args.strip_exif && output_format == image::ImageFormat::Jpeg {
+        out = exif::copy_jpeg_exif(&source_bytes, out);
+    }
+
+    std::fs::write(output_filename, out).unwrap();
+}
+
+fn local_gif(path: &str, args: &Args) {
+    let (stem, extension) = get_filename_and_extension(path);
+    let bytes = std::fs::read(path).expect("No such file or directory");
+    let out = video::strangify_gif(&bytes, args.scale);
+
+    let id = Uuid::new_v4();
+    let output_filename = format!("./{}_{}.{}", stem, id.to_string(), extension);
+    println!("{output_filename}");
+
+    std::fs::write(output_filename, out).unwrap();
+}
+
+#[cfg(feature = "video-ffmpeg")]
+fn local_video(path: &str, args: &Args) {
+    let (stem, extension) = get_filename_and_extension(path);
+    let id = Uuid::new_v4();
+    let output_filename = format!("./{}_{}.{}", stem, id.to_string(), extension);
+
+    video::ffmpeg_video::strangify_video(path, &output_filename, args.scale)
+        .expect("failed to strangify video");
+    println!("{output_filename}");
 }
 
-fn get_url_image(url: String) -> (DynamicImage, String, String) {
-    let mut bytes_reader = ureq::get(&url).call().unwrap().into_reader();
-    let mut buffer = vec![];
-    bytes_reader.read_to_end(&mut buffer).unwrap();
+fn get_url_image(
+    url: String,
+    guard: &FetchGuard,
+) -> Result<(DynamicImage, String, String, Vec<u8>), fetch::FetchError> {
+    let buffer = guard.fetch(&url)?;
+    let img = decode_image(&buffer, guard)?;
+
+    let (stem, extension) = get_filename_and_extension(&url);
+    Ok((img, stem, extension, buffer))
+}
+
+/// Peeks `buffer`'s dimensions against `guard`'s pixel budget before doing the full decode, then
+/// auto-orients using EXIF from the same bytes. Split out of `get_url_image` so callers that need
+/// the raw bytes first (to sniff for an animated GIF, say) aren't forced to decode twice.
+fn decode_image(buffer: &[u8], guard: &FetchGuard) -> Result<DynamicImage, fetch::FetchError> {
+    let (width, height) = image::io::Reader::new(Cursor::new(buffer))
+        .with_guessed_format()
+        .map_err(|_| fetch::FetchError::NotAnImage)?
+        .into_dimensions()
+        .map_err(|_| fetch::FetchError::NotAnImage)?;
+    guard.check_dimensions(width, height)?;
 
     let img = image::io::Reader::new(Cursor::new(buffer))
         .with_guessed_format()
         .unwrap()
         .decode()
-        .unwrap();
-
-    let (stem, extension) = get_filename_and_extension(&url);
-    (img, stem, extension)
-}
-
-fn strangify(img: &mut DynamicImage, scale: f32) {
-    let model_bytes = include_bytes!("../model/seeta_fd_frontal_v1.0.bin");
-    let model = read_model(model_bytes.as_slice()).unwrap();
-    let mut detector = rustface::create_detector_with_model(model);
-
-    detector.set_min_face_size(20);
-    detector.set_score_thresh(2.0);
-    detector.set_pyramid_scale_factor(0.8);
-    detector.set_slide_window_step(4, 4);
-
-    let width = img.width();
-    let height = img.height();
-
-    let gray = img.to_luma8();
-    let mut image = ImageData::new(&gray, width, height);
-
-    let faces = [
-        image::load_from_memory(include_bytes!("../strangeway/strangeway0.png").as_slice())
-            .unwrap(),
-        image::load_from_memory(include_bytes!("../strangeway/strangeway1.png").as_slice())
-            .unwrap(),
-    ];
-
-    for face in detector.detect(&mut image).into_iter() {
-        let face_id = if random() { 1 } else { 0 };
-        let bbox = face.bbox();
-        let box_width = bbox.width();
-        let box_w_upscale = (box_width as f32 * scale) as u32;
-        let box_height = bbox.height();
-        let box_h_upscale = (box_height as f32 * scale) as u32;
-        let x_offset = box_w_upscale / 2;
-        let y_offset = box_h_upscale / 2;
-
-        let scaled_face = faces[face_id].resize(
-            box_width + box_w_upscale,
-            box_height + box_h_upscale,
-            FilterType::CatmullRom,
-        );
-
-        overlay(
-            img,
-            &scaled_face,
-            face.bbox().x() as i64 - x_offset as i64,
-            face.bbox().y() as i64 - y_offset as i64,
-        );
-    }
+        .map_err(|_| fetch::FetchError::NotAnImage)?;
+    Ok(exif::auto_orient(img, buffer))
 }
 
 fn get_filename_and_extension(path: &str) -> (String, String) {
@@ -0,0 +1,81 @@
+use image::{
+    imageops::{overlay, FilterType},
+    DynamicImage,
+};
+use rand::random;
+use rustface::{read_model, Detector, ImageData};
+
+/// Owns the face detector and the two overlay faces so they can be built once and reused
+/// across many frames. Constructing the detector from the model file is the expensive part
+/// of strangification, so video callers should keep a single `Strangifier` alive for the
+/// whole clip instead of rebuilding it per frame.
+pub struct Strangifier {
+    detector: Box<dyn Detector>,
+    faces: [DynamicImage; 2],
+}
+
+impl Strangifier {
+    pub fn new() -> Self {
+        let model_bytes = include_bytes!("../model/seeta_fd_frontal_v1.0.bin");
+        let model = read_model(model_bytes.as_slice()).unwrap();
+        let mut detector = rustface::create_detector_with_model(model);
+
+        detector.set_min_face_size(20);
+        detector.set_score_thresh(2.0);
+        detector.set_pyramid_scale_factor(0.8);
+        detector.set_slide_window_step(4, 4);
+
+        let faces = [
+            image::load_from_memory(include_bytes!("../strangeway/strangeway0.png").as_slice())
+                .unwrap(),
+            image::load_from_memory(include_bytes!("../strangeway/strangeway1.png").as_slice())
+                .unwrap(),
+        ];
+
+        Self { detector, faces }
+    }
+
+    pub fn strangify(&mut self, img: &mut DynamicImage, scale: f32) {
+        let width = img.width();
+        let height = img.height();
+
+        let gray = img.to_luma8();
+        let mut image = ImageData::new(&gray, width, height);
+
+        for face in self.detector.detect(&mut image).into_iter() {
+            let face_id = if random() { 1 } else { 0 };
+            let bbox = face.bbox();
+            let box_width = bbox.width();
+            let box_w_upscale = (box_width as f32 * scale) as u32;
+            let box_height = bbox.height();
+            let box_h_upscale = (box_height as f32 * scale) as u32;
+            let x_offset = box_w_upscale / 2;
+            let y_offset = box_h_upscale / 2;
+
+            let scaled_face = self.faces[face_id].resize(
+                box_width + box_w_upscale,
+                box_height + box_h_upscale,
+                FilterType::CatmullRom,
+            );
+
+            overlay(
+                img,
+                &scaled_face,
+                face.bbox().x() as i64 - x_offset as i64,
+                face.bbox().y() as i64 - y_offset as i64,
+            );
+        }
+    }
+}
+
+impl Default for Strangifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Strangifies a single still image. Builds a fresh `Strangifier`, so callers that need to
+/// process many frames (animated GIFs, video) should use `Strangifier` directly and reuse it.
+pub fn strangify(img: &mut DynamicImage, scale: f32) {
+    Strangifier::new().strangify(img, scale);
+}
@@ -0,0 +1,171 @@
+use std::io::Cursor;
+
+use image::codecs::gif::{GifDecoder, GifEncoder};
+use image::{AnimationDecoder, DynamicImage, Frame};
+
+use crate::strangify::Strangifier;
+
+/// Sniffs the GIF87a/GIF89a magic bytes so callers (the web server included) can detect an
+/// animated input regardless of what extension, if any, the source URL or upload carries.
+pub fn is_gif(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")
+}
+
+/// Demuxes an animated GIF into its frames, strangifies each one with a single shared
+/// `Strangifier`, then re-muxes the result as a new GIF preserving the original frame delays.
+pub fn strangify_gif(bytes: &[u8], scale: f32) -> Vec<u8> {
+    let decoder = GifDecoder::new(Cursor::new(bytes)).expect("not a valid gif");
+
+    let mut strangifier = Strangifier::new();
+    let mut out_frames = Vec::new();
+
+    for frame in decoder.into_frames() {
+        let frame = frame.expect("failed to decode gif frame");
+        let delay = frame.delay();
+        let mut img = DynamicImage::ImageRgba8(frame.into_buffer());
+
+        strangifier.strangify(&mut img, scale);
+
+        out_frames.push(Frame::from_parts(img.to_rgba8(), 0, 0, delay));
+    }
+
+    let mut out = Vec::new();
+    GifEncoder::new(&mut out)
+        .encode_frames(out_frames)
+        .expect("failed to encode gif");
+    out
+}
+
+/// MP4/WebM support via `ffmpeg-next`. Off by default since it links against the system
+/// ffmpeg libraries; enable with the `video-ffmpeg` cargo feature.
+#[cfg(feature = "video-ffmpeg")]
+pub mod ffmpeg_video {
+    use super::*;
+    use ffmpeg_next as ffmpeg;
+
+    /// Demuxes `input_path` frame-by-frame, strangifies each frame with a single shared
+    /// `Strangifier`, and re-encodes with H.264 at `output_path`, preserving the source fps.
+    pub fn strangify_video(input_path: &str, output_path: &str, scale: f32) -> Result<(), ffmpeg::Error> {
+        ffmpeg::init()?;
+
+        let mut ictx = ffmpeg::format::input(&input_path)?;
+        let input_stream_index = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .expect("no video stream in input")
+            .index();
+
+        let context_decoder = ffmpeg::codec::context::Context::from_parameters(
+            ictx.stream(input_stream_index).unwrap().parameters(),
+        )?;
+        let mut decoder = context_decoder.decoder().video()?;
+        let fps = ictx.stream(input_stream_index).unwrap().avg_frame_rate();
+
+        let mut octx = ffmpeg::format::output(&output_path)?;
+        let codec =
+            ffmpeg::encoder::find(ffmpeg::codec::Id::H264).expect("no h264 encoder available");
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()?;
+        encoder.set_width(decoder.width());
+        encoder.set_height(decoder.height());
+        encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+        encoder.set_time_base(fps.invert());
+
+        let mut encoder = encoder.open_as(codec)?;
+        let mut ost = octx.add_stream(codec)?;
+        ost.set_parameters(&encoder);
+        octx.write_header()?;
+
+        let mut scaler = ffmpeg::software::scaling::Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            ffmpeg::format::Pixel::RGB24,
+            decoder.width(),
+            decoder.height(),
+            ffmpeg::software::scaling::Flags::BILINEAR,
+        )?;
+
+        let mut strangifier = Strangifier::new();
+
+        for (stream, packet) in ictx.packets() {
+            if stream.index() != input_stream_index {
+                continue;
+            }
+            decoder.send_packet(&packet)?;
+
+            let mut decoded = ffmpeg::util::frame::Video::empty();
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                let mut rgb_frame = ffmpeg::util::frame::Video::empty();
+                scaler.run(&decoded, &mut rgb_frame)?;
+
+                let mut img = rgb_frame_to_dynamic_image(&rgb_frame);
+                strangifier.strangify(&mut img, scale);
+
+                let mut out_frame = dynamic_image_to_rgb_frame(&img);
+                out_frame.set_pts(decoded.pts());
+
+                encoder.send_frame(&out_frame)?;
+                drain_encoder(&mut encoder, &mut octx)?;
+            }
+        }
+
+        encoder.send_eof()?;
+        drain_encoder(&mut encoder, &mut octx)?;
+        octx.write_trailer()?;
+
+        Ok(())
+    }
+
+    fn drain_encoder(
+        encoder: &mut ffmpeg::encoder::Video,
+        octx: &mut ffmpeg::format::context::Output,
+    ) -> Result<(), ffmpeg::Error> {
+        let mut encoded = ffmpeg::Packet::empty();
+        while encoder.receive_packet(&mut encoded).is_ok() {
+            encoded.set_stream(0);
+            encoded.write_interleaved(octx)?;
+        }
+        Ok(())
+    }
+
+    /// `frame.data(0)` is laid out in rows of `frame.stride(0)` bytes, which ffmpeg pads past
+    /// `width * 3` for alignment on many resolutions — copying it as one packed slice would
+    /// panic on `RgbImage::from_raw`'s length check (or silently shear the image on the
+    /// resolutions where it happens to match). Copy row-by-row using the real stride instead.
+    fn rgb_frame_to_dynamic_image(frame: &ffmpeg::util::frame::Video) -> DynamicImage {
+        let width = frame.width();
+        let height = frame.height();
+        let stride = frame.stride(0);
+        let row_bytes = width as usize * 3;
+        let data = frame.data(0);
+
+        let mut packed = Vec::with_capacity(row_bytes * height as usize);
+        for row in 0..height as usize {
+            let start = row * stride;
+            packed.extend_from_slice(&data[start..start + row_bytes]);
+        }
+
+        let buffer =
+            image::RgbImage::from_raw(width, height, packed).expect("frame buffer size mismatch");
+        DynamicImage::ImageRgb8(buffer)
+    }
+
+    fn dynamic_image_to_rgb_frame(img: &DynamicImage) -> ffmpeg::util::frame::Video {
+        let rgb = img.to_rgb8();
+        let mut frame =
+            ffmpeg::util::frame::Video::new(ffmpeg::format::Pixel::RGB24, rgb.width(), rgb.height());
+
+        let stride = frame.stride(0);
+        let row_bytes = rgb.width() as usize * 3;
+        let data = frame.data_mut(0);
+        for (row, src_row) in rgb.chunks_exact(row_bytes).enumerate() {
+            let start = row * stride;
+            data[start..start + row_bytes].copy_from_slice(src_row);
+        }
+
+        frame
+    }
+}